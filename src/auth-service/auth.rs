@@ -1,15 +1,19 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use tokio::sync::RwLock;
 
+use crate::mfa::{ChallengeVerifier, TotpVerifier};
 use crate::{sessions::Sessions, users::Users};
 
 use tonic::{Request, Response, Status};
+use uuid::Uuid;
 
 use authentication::auth_server::Auth;
 use authentication::{
+    AuthenticateRequest, AuthenticateResponse, Challenge, ChallengeResponse, Question,
     SignInRequest, SignInResponse, SignOutRequest, SignOutResponse, SignUpRequest, SignUpResponse,
-    StatusCode,
+    StatusCode, VerificationResponse,
 };
 
 pub mod authentication {
@@ -20,9 +24,17 @@ pub mod authentication {
 pub use authentication::auth_server::AuthServer;
 pub use tonic::transport::Server;
 
+// A challenge issued by `sign_in`, pending a matching `respond_to_challenge` call.
+struct PendingChallenge {
+    user_uuid: String,
+    mfa_secret: String,
+}
+
 pub struct AuthService {
     users_service: Arc<RwLock<dyn Users + Send + Sync>>,
     sessions_service: Arc<RwLock<dyn Sessions + Send + Sync>>,
+    verifier: Arc<dyn ChallengeVerifier + Send + Sync>,
+    pending_challenges: RwLock<HashMap<String, PendingChallenge>>,
 }
 
 impl AuthService {
@@ -33,6 +45,8 @@ impl AuthService {
         Self {
             users_service,
             sessions_service,
+            verifier: Arc::new(TotpVerifier),
+            pending_challenges: RwLock::new(HashMap::new()),
         }
     }
 }
@@ -52,7 +66,8 @@ impl Auth for AuthService {
             .users_service
             .read()
             .await
-            .get_user_uuid(req.username, req.password);
+            .get_user_uuid(req.username, req.password)
+            .await;
 
         // Match on `result`. If `result` is `None` return a SignInResponse with a the `status_code` set to `Failure`
         // and `user_uuid`/`session_token` set to empty strings.
@@ -63,24 +78,55 @@ impl Auth for AuthService {
                     status_code: StatusCode::Failure.into(),
                     user_uuid: "".to_owned(),
                     session_token: "".to_owned(),
+                    challenge: None,
                 };
 
                 return Ok(Response::new(reply));
             }
         };
 
+        // Password verified. If the user has enrolled MFA, stop here and hand back a challenge
+        // instead of a session; `respond_to_challenge` is what actually calls `create_session`.
+        if let Some(mfa_secret) = self.users_service.read().await.mfa_secret(&user_uuid).await {
+            let challenge_id = Uuid::new_v4().to_string();
+
+            self.pending_challenges.write().await.insert(
+                challenge_id.clone(),
+                PendingChallenge {
+                    user_uuid,
+                    mfa_secret,
+                },
+            );
+
+            let reply = SignInResponse {
+                status_code: StatusCode::ChallengeRequired.into(),
+                user_uuid: "".to_owned(),
+                session_token: "".to_owned(),
+                challenge: Some(Challenge {
+                    challenge_id,
+                    questions: vec![Question {
+                        prompt: "Enter your 6-digit authenticator code".to_owned(),
+                    }],
+                }),
+            };
+
+            return Ok(Response::new(reply));
+        }
+
         // Create new session using `sessions_service`.
         let session_token = self
             .sessions_service
             .write()
             .await
-            .create_session(&user_uuid);
+            .create_session(&user_uuid)
+            .await;
 
         // Create a `SignInResponse` with `status_code` set to `Success`
         let reply: SignInResponse = SignInResponse {
             status_code: StatusCode::Success.into(),
             user_uuid: user_uuid,
             session_token: session_token,
+            challenge: None,
         };
 
         Ok(Response::new(reply))
@@ -99,7 +145,8 @@ impl Auth for AuthService {
             .users_service
             .write()
             .await
-            .create_user(req.username, req.password);
+            .create_user(req.username, req.password)
+            .await;
 
         // Return a `SignUpResponse` with the appropriate `status_code` based on `result`.
         let reply = match result {
@@ -126,7 +173,8 @@ impl Auth for AuthService {
         self.sessions_service
             .write()
             .await
-            .delete_session(&req.session_token);
+            .delete_session(&req.session_token)
+            .await;
 
         // Create `SignOutResponse` with `status_code` set to `Success`
         let reply: SignOutResponse = SignOutResponse {
@@ -135,6 +183,87 @@ impl Auth for AuthService {
 
         Ok(Response::new(reply))
     }
+
+    async fn authenticate(
+        &self,
+        request: Request<AuthenticateRequest>,
+    ) -> Result<Response<AuthenticateResponse>, Status> {
+        let req = request.into_inner();
+
+        // Resolve the session token to a `user_uuid` using `sessions_service`, the same lookup
+        // `AuthInterceptor` performs on behalf of other microservices.
+        let result = self
+            .sessions_service
+            .read()
+            .await
+            .validate_session(&req.session_token)
+            .await;
+
+        let reply = match result {
+            Some(user_uuid) => AuthenticateResponse {
+                status_code: StatusCode::Success.into(),
+                user_uuid,
+            },
+            None => AuthenticateResponse {
+                status_code: StatusCode::Failure.into(),
+                user_uuid: "".to_owned(),
+            },
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    async fn respond_to_challenge(
+        &self,
+        request: Request<ChallengeResponse>,
+    ) -> Result<Response<VerificationResponse>, Status> {
+        // Unlike the baseline handlers' `println!("Got a request: {:?}", request)`, this RPC's
+        // request carries the caller's MFA answers, which shouldn't be logged.
+        let req = request.into_inner();
+
+        // The challenge is single-use: removing it here means a replayed `challenge_id` always
+        // fails, whether or not the first attempt succeeded.
+        let pending = self
+            .pending_challenges
+            .write()
+            .await
+            .remove(&req.challenge_id);
+
+        let Some(pending) = pending else {
+            let reply = VerificationResponse {
+                status_code: StatusCode::Failure.into(),
+                user_uuid: "".to_owned(),
+                session_token: "".to_owned(),
+            };
+
+            return Ok(Response::new(reply));
+        };
+
+        if !self.verifier.verify(&pending.mfa_secret, &req.answers) {
+            let reply = VerificationResponse {
+                status_code: StatusCode::Failure.into(),
+                user_uuid: "".to_owned(),
+                session_token: "".to_owned(),
+            };
+
+            return Ok(Response::new(reply));
+        }
+
+        let session_token = self
+            .sessions_service
+            .write()
+            .await
+            .create_session(&pending.user_uuid)
+            .await;
+
+        let reply = VerificationResponse {
+            status_code: StatusCode::Success.into(),
+            user_uuid: pending.user_uuid,
+            session_token,
+        };
+
+        Ok(Response::new(reply))
+    }
 }
 
 #[cfg(test)]
@@ -166,7 +295,7 @@ mod tests {
     async fn sign_in_should_fail_if_incorrect_password() {
         let mut users_service = UsersImpl::default();
 
-        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned());
+        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned()).await;
 
         let users_service = Arc::new(RwLock::new(users_service));
         let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
@@ -189,7 +318,7 @@ mod tests {
     async fn sign_in_should_succeed() {
         let mut users_service = UsersImpl::default();
 
-        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned());
+        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned()).await;
 
         let users_service = Arc::new(RwLock::new(users_service));
         let sessions_service: Arc<RwLock<SessionsImpl>> =
@@ -213,7 +342,7 @@ mod tests {
     async fn sign_up_should_fail_if_username_exists() {
         let mut users_service = UsersImpl::default();
 
-        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned());
+        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned()).await;
 
         let users_service = Arc::new(RwLock::new(users_service));
         let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
@@ -262,4 +391,150 @@ mod tests {
 
         assert_eq!(result.into_inner().status_code, StatusCode::Success.into());
     }
+
+    #[tokio::test]
+    async fn authenticate_should_fail_for_invalid_token() {
+        let users_service = Arc::new(RwLock::new(UsersImpl::default()));
+        let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
+
+        let auth_service = AuthService::new(users_service, sessions_service);
+
+        let request = tonic::Request::new(AuthenticateRequest {
+            session_token: "not a real token".to_owned(),
+        });
+
+        let result = auth_service.authenticate(request).await.unwrap().into_inner();
+
+        assert_eq!(result.status_code, StatusCode::Failure.into());
+        assert_eq!(result.user_uuid.is_empty(), true);
+    }
+
+    #[tokio::test]
+    async fn authenticate_should_succeed_for_a_session_issued_by_sign_in() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned()).await;
+
+        let users_service = Arc::new(RwLock::new(users_service));
+        let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
+
+        let auth_service = AuthService::new(users_service, sessions_service);
+
+        let sign_in_request = tonic::Request::new(SignInRequest {
+            username: "123456".to_owned(),
+            password: "654321".to_owned(),
+        });
+        let sign_in_result = auth_service
+            .sign_in(sign_in_request)
+            .await
+            .unwrap()
+            .into_inner();
+
+        let request = tonic::Request::new(AuthenticateRequest {
+            session_token: sign_in_result.session_token,
+        });
+        let result = auth_service.authenticate(request).await.unwrap().into_inner();
+
+        assert_eq!(result.status_code, StatusCode::Success.into());
+        assert_eq!(result.user_uuid, sign_in_result.user_uuid);
+    }
+
+    #[tokio::test]
+    async fn sign_in_should_return_a_challenge_when_mfa_is_enrolled() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned()).await;
+        let user_uuid = users_service
+            .get_user_uuid("123456".to_owned(), "654321".to_owned())
+            .await
+            .unwrap();
+        users_service.set_mfa_secret(&user_uuid, "supersecret".to_owned()).await;
+
+        let users_service = Arc::new(RwLock::new(users_service));
+        let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
+
+        let auth_service = AuthService::new(users_service, sessions_service);
+
+        let request = tonic::Request::new(SignInRequest {
+            username: "123456".to_owned(),
+            password: "654321".to_owned(),
+        });
+
+        let result = auth_service.sign_in(request).await.unwrap().into_inner();
+
+        assert_eq!(result.status_code, StatusCode::ChallengeRequired.into());
+        assert_eq!(result.session_token.is_empty(), true);
+        assert_eq!(result.challenge.is_some(), true);
+    }
+
+    #[tokio::test]
+    async fn respond_to_challenge_should_fail_for_an_unknown_challenge_id() {
+        let users_service = Arc::new(RwLock::new(UsersImpl::default()));
+        let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
+
+        let auth_service = AuthService::new(users_service, sessions_service);
+
+        let request = tonic::Request::new(ChallengeResponse {
+            challenge_id: "not a real challenge".to_owned(),
+            answers: vec!["000000".to_owned()],
+        });
+
+        let result = auth_service
+            .respond_to_challenge(request)
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(result.status_code, StatusCode::Failure.into());
+        assert_eq!(result.session_token.is_empty(), true);
+    }
+
+    #[tokio::test]
+    async fn respond_to_challenge_should_succeed_with_the_current_totp_code() {
+        use totp_rs::{Algorithm, TOTP};
+
+        let mfa_secret = "supersecretsupersecret".to_owned();
+
+        let mut users_service = UsersImpl::default();
+        let _ = users_service.create_user("123456".to_owned(), "654321".to_owned()).await;
+        let user_uuid = users_service
+            .get_user_uuid("123456".to_owned(), "654321".to_owned())
+            .await
+            .unwrap();
+        users_service.set_mfa_secret(&user_uuid, mfa_secret.clone()).await;
+
+        let users_service = Arc::new(RwLock::new(users_service));
+        let sessions_service = Arc::new(RwLock::new(SessionsImpl::default()));
+
+        let auth_service = AuthService::new(users_service, sessions_service);
+
+        let sign_in_request = tonic::Request::new(SignInRequest {
+            username: "123456".to_owned(),
+            password: "654321".to_owned(),
+        });
+        let sign_in_result = auth_service
+            .sign_in(sign_in_request)
+            .await
+            .unwrap()
+            .into_inner();
+        let challenge_id = sign_in_result.challenge.unwrap().challenge_id;
+
+        let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, mfa_secret.as_bytes().to_vec()).unwrap();
+        let code = totp.generate_current().unwrap();
+
+        let request = tonic::Request::new(ChallengeResponse {
+            challenge_id,
+            answers: vec![code],
+        });
+
+        let result = auth_service
+            .respond_to_challenge(request)
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(result.status_code, StatusCode::Success.into());
+        assert_eq!(result.user_uuid, user_uuid);
+        assert_eq!(result.session_token.is_empty(), false);
+    }
 }
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+use crate::sessions::Sessions;
+
+/// Inserted into request extensions by [`AuthInterceptor`] so handlers can recover the caller's
+/// `user_uuid` without re-parsing the bearer token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthenticatedUser(pub String);
+
+/// A `tonic` [`Interceptor`] that gates an RPC on a valid session, for use with
+/// [`tonic::service::interceptor::InterceptedService`]. Other microservices can wrap their own
+/// services with it on their `Server::builder()` call; `main.rs` adds the `AuthServer` itself
+/// without it.
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    sessions_service: Arc<RwLock<dyn Sessions + Send + Sync>>,
+}
+
+impl AuthInterceptor {
+    pub fn new(sessions_service: Arc<RwLock<dyn Sessions + Send + Sync>>) -> Self {
+        Self { sessions_service }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing bearer session token"))?
+            .to_owned();
+
+        // `Interceptor::call` isn't async, and `validate_session` now is (it backs `SessionsPg`'s
+        // `.await`-based sqlx calls too), so this is the one legitimate sync/async bridge in the
+        // service rather than a lock held across a blocking DB round-trip. `block_in_place` moves
+        // the wait off the async scheduler, so we can afford to wait for the read guard with
+        // `.read().await` instead of `try_read`: a writer holding the lock for a `create_session`
+        // round-trip is normal concurrent load, not contention worth rejecting requests over.
+        let sessions_service = self.sessions_service.clone();
+        let user_uuid = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                sessions_service
+                    .read()
+                    .await
+                    .validate_session(&token)
+                    .await
+                    .ok_or_else(|| Status::unauthenticated("Invalid or expired session token"))
+            })
+        })?;
+
+        request.extensions_mut().insert(AuthenticatedUser(user_uuid));
+
+        Ok(request)
+    }
+}
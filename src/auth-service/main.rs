@@ -1,13 +1,23 @@
 mod auth;
+mod interceptor;
+mod mfa;
 mod sessions;
+#[cfg(feature = "sso_login")]
+mod sso;
 mod users;
 
+use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 
 use auth::*;
-use sessions::{Sessions, SessionsImpl};
+use sessions::{Sessions, SessionsImpl, SessionsPg};
 use tokio::sync::RwLock;
-use users::{Users, UsersImpl};
+use tokio::time::interval;
+use users::{Users, UsersImpl, UsersPg};
+
+// Defaults used when the matching env vars below are unset.
+const DEFAULT_REAPER_INTERVAL_SECONDS: u64 = 60;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -16,20 +26,73 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Port 50051 is the recommended gRPC port.
     let addr = "[::0]:50051".parse()?;
 
-    // Create user service instance
-    let users_service: Arc<RwLock<dyn Users + Send + Sync + 'static>> =
-        Arc::new(RwLock::new(UsersImpl::default()));
+    // Required up front and for either backend: a hardcoded fallback here would mean anyone
+    // could forge session tokens if the operator forgot to set it.
+    let jwt_secret = env::var("JWT_SECRET")
+        .map_err(|_| "JWT_SECRET environment variable must be set")?;
+
+    // When `DATABASE_URL` is set, persist users/sessions in Postgres; otherwise fall back to the
+    // in-memory backends, which is what the test suite exercises.
+    let (users_service, sessions_service): (
+        Arc<RwLock<dyn Users + Send + Sync + 'static>>,
+        Arc<RwLock<dyn Sessions + Send + Sync + 'static>>,
+    ) = match env::var("DATABASE_URL") {
+        Ok(database_url) => {
+            let pool = sqlx::postgres::PgPoolOptions::new()
+                .connect(&database_url)
+                .await?;
+
+            sqlx::migrate!("./migrations").run(&pool).await?;
 
-    let sessions_service: Arc<RwLock<dyn Sessions + Send + Sync + 'static>> =
-        Arc::new(RwLock::new(SessionsImpl::default()));
+            (
+                Arc::new(RwLock::new(UsersPg::new(pool.clone()))),
+                Arc::new(RwLock::new(SessionsPg::new(pool, jwt_secret))),
+            )
+        }
+        Err(_) => (
+            Arc::new(RwLock::new(UsersImpl::default())),
+            Arc::new(RwLock::new(SessionsImpl::new(jwt_secret))),
+        ),
+    };
+
+    spawn_session_reaper(sessions_service.clone());
 
     let auth_service = AuthService::new(users_service, sessions_service);
 
+    // `AuthServer` is attached plainly, not behind `AuthInterceptor`: `SignIn`/`SignUp` have no
+    // session yet to present, and `Authenticate` is the RPC that verifies one, so gating the
+    // auth service's own endpoints on a valid session would make it impossible to ever obtain
+    // one. `AuthInterceptor` is `pub` from `interceptor` for other microservices to apply to
+    // *their* `Server::builder()` the same way, once they've got a session by calling us.
+    let auth_server = AuthServer::new(auth_service);
+
     // Instantiate gRPC server
     Server::builder()
-        .add_service(AuthServer::new(auth_service))
+        .add_service(auth_server)
         .serve(addr)
         .await?;
 
     Ok(())
 }
+
+// Periodically drops stale sessions so they don't accumulate forever. Each sweep only holds the
+// `RwLock` write guard for the duration of `purge_expired`, so it never blocks `sign_in`/`sign_out`
+// for longer than a single purge.
+fn spawn_session_reaper(sessions_service: Arc<RwLock<dyn Sessions + Send + Sync + 'static>>) {
+    // `Duration::from_secs(0)` would make `interval` panic ("period must be non-zero"), so an
+    // explicit `0` falls back to the default instead of taking down the reaper task.
+    let reaper_interval = env::var("SESSION_REAPER_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|seconds| *seconds != 0)
+        .unwrap_or(DEFAULT_REAPER_INTERVAL_SECONDS);
+
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(reaper_interval));
+
+        loop {
+            ticker.tick().await;
+            sessions_service.write().await.purge_expired().await;
+        }
+    });
+}
@@ -0,0 +1,52 @@
+use totp_rs::{Algorithm, TOTP};
+
+/// Checks a challenge's answers against a per-user secret. Kept as a trait so additional factors
+/// (e.g. WebAuthn, SMS codes) can be added as further implementations. `AuthService::new` always
+/// installs `TotpVerifier`; there is no constructor that takes another verifier.
+pub trait ChallengeVerifier {
+    fn verify(&self, secret: &str, answers: &[String]) -> bool;
+}
+
+/// Verifies a single TOTP code, RFC 6238 style: 6 digits, SHA-1, 30-second step.
+pub struct TotpVerifier;
+
+impl ChallengeVerifier for TotpVerifier {
+    fn verify(&self, secret: &str, answers: &[String]) -> bool {
+        let Some(code) = answers.first() else {
+            return false;
+        };
+
+        let Ok(totp) = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret.as_bytes().to_vec()) else {
+            return false;
+        };
+
+        totp.check_current(code).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_verifier_should_reject_a_wrong_code() {
+        let verifier = TotpVerifier;
+
+        let result = verifier.verify("supersecretsupersecret", &["000000".to_owned()]);
+
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn totp_verifier_should_accept_the_current_code() {
+        let verifier = TotpVerifier;
+        let secret = "supersecretsupersecret";
+
+        let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret.as_bytes().to_vec()).unwrap();
+        let code = totp.generate_current().unwrap();
+
+        let result = verifier.verify(secret, &[code]);
+
+        assert_eq!(result, true);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Default session lifetime, used when `JWT_TTL_SECONDS` is unset.
+const DEFAULT_TTL_SECONDS: u64 = 60 * 60;
+
+#[tonic::async_trait]
+pub trait Sessions {
+    async fn create_session(&mut self, user_uuid: &str) -> String;
+    async fn delete_session(&mut self, session_token: &str);
+    async fn validate_session(&self, session_token: &str) -> Option<String>;
+    /// Drops sessions past their `exp`/`expires_at`. Called periodically by the reaper task
+    /// spawned from `main.rs`; safe to call as often as needed since it's a no-op when nothing
+    /// has expired yet.
+    async fn purge_expired(&mut self);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    iat: usize,
+    exp: usize,
+    jti: String,
+}
+
+pub struct SessionsImpl {
+    // Secret used to sign/verify session tokens. Loaded once by the caller (see
+    // `main::jwt_secret`, which fails fast if `JWT_SECRET` is unset) rather than read from the
+    // environment on every call with a hardcoded fallback.
+    jwt_secret: String,
+    // `jti`s that have been explicitly signed out, mapped to the `exp` of the token they came
+    // from. Rejected in `validate_session` and swept by `purge_expired` once their `exp` plus the
+    // validation leeway passes, since at that point the token would fail expiry validation anyway.
+    revoked_jtis: HashMap<String, usize>,
+}
+
+impl SessionsImpl {
+    pub fn new(jwt_secret: String) -> Self {
+        Self {
+            jwt_secret,
+            revoked_jtis: HashMap::new(),
+        }
+    }
+
+    // Session TTL, in seconds, configurable through the environment.
+    fn ttl_seconds() -> u64 {
+        env::var("JWT_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS)
+    }
+
+    fn now_as_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock is before the Unix epoch")
+            .as_secs()
+    }
+}
+
+impl Default for SessionsImpl {
+    fn default() -> Self {
+        // Only reachable from tests/dev code paths below; `main` always goes through `new` with
+        // a secret it loaded from `JWT_SECRET`, exiting with an error if unset.
+        Self::new("test-only-insecure-secret".to_owned())
+    }
+}
+
+#[tonic::async_trait]
+impl Sessions for SessionsImpl {
+    async fn create_session(&mut self, user_uuid: &str) -> String {
+        let now = Self::now_as_secs();
+
+        let claims = Claims {
+            sub: user_uuid.to_owned(),
+            iat: now as usize,
+            exp: (now + Self::ttl_seconds()) as usize,
+            jti: Uuid::new_v4().to_string(),
+        };
+
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )
+        .expect("JWT encoding should not fail")
+    }
+
+    async fn delete_session(&mut self, session_token: &str) {
+        // Revoking the `jti` is enough to make the token unusable, even though it's stateless
+        // and will keep passing signature/expiry checks until it expires.
+        if let Ok(data) = decode::<Claims>(
+            session_token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        ) {
+            self.revoked_jtis.insert(data.claims.jti, data.claims.exp);
+        }
+    }
+
+    async fn validate_session(&self, session_token: &str) -> Option<String> {
+        let data = decode::<Claims>(
+            session_token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .ok()?;
+
+        if self.revoked_jtis.contains_key(&data.claims.jti) {
+            return None;
+        }
+
+        Some(data.claims.sub)
+    }
+
+    async fn purge_expired(&mut self) {
+        let now = Self::now_as_secs() as usize;
+        let leeway = Validation::default().leeway as usize;
+        self.revoked_jtis.retain(|_, exp| *exp + leeway > now);
+    }
+}
+
+/// Postgres-backed `Sessions`, for when `DATABASE_URL` is set. Each issued token's `jti` is
+/// mirrored into the `sessions` table, which is the source of truth for validation instead of
+/// the in-memory revocation set `SessionsImpl` uses; a "revoke" is just a row deletion.
+///
+/// `Sessions` is an async trait, so every method below `.await`s `sqlx` directly rather than
+/// bridging into the async pool with `block_in_place`/`block_on` while the caller's `RwLock`
+/// guard is held.
+pub struct SessionsPg {
+    pool: PgPool,
+    jwt_secret: String,
+}
+
+impl SessionsPg {
+    pub fn new(pool: PgPool, jwt_secret: String) -> Self {
+        Self { pool, jwt_secret }
+    }
+}
+
+#[tonic::async_trait]
+impl Sessions for SessionsPg {
+    async fn create_session(&mut self, user_uuid: &str) -> String {
+        let now = SessionsImpl::now_as_secs();
+        let exp = now + SessionsImpl::ttl_seconds();
+
+        let claims = Claims {
+            sub: user_uuid.to_owned(),
+            iat: now as usize,
+            exp: exp as usize,
+            jti: Uuid::new_v4().to_string(),
+        };
+
+        let session_token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )
+        .expect("JWT encoding should not fail");
+
+        // `jti` is always a fresh Uuid v4 we just generated, but `user_uuid` comes from the
+        // caller; a non-UUID `user_uuid` shouldn't panic the whole service, it should just mean
+        // the session isn't persisted (the caller still gets a signed token back, but
+        // `validate_session` rejects it since there is no matching row).
+        let jti: Uuid = claims.jti.parse().expect("jti is always a fresh Uuid v4");
+
+        if let Ok(user_uuid) = user_uuid.parse::<Uuid>() {
+            let _ = sqlx::query(
+                "INSERT INTO sessions (jti, user_uuid, expires_at) VALUES ($1, $2, to_timestamp($3))",
+            )
+            .bind(jti)
+            .bind(user_uuid)
+            .bind(exp as f64)
+            .execute(&self.pool)
+            .await;
+        }
+
+        session_token
+    }
+
+    async fn delete_session(&mut self, session_token: &str) {
+        let Ok(data) = decode::<Claims>(
+            session_token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        ) else {
+            return;
+        };
+
+        let Ok(jti) = data.claims.jti.parse::<Uuid>() else {
+            return;
+        };
+
+        let _ = sqlx::query("DELETE FROM sessions WHERE jti = $1")
+            .bind(jti)
+            .execute(&self.pool)
+            .await;
+    }
+
+    async fn validate_session(&self, session_token: &str) -> Option<String> {
+        let data = decode::<Claims>(
+            session_token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .ok()?;
+
+        let jti: Uuid = data.claims.jti.parse().ok()?;
+
+        sqlx::query_scalar::<_, Uuid>("SELECT user_uuid FROM sessions WHERE jti = $1 AND expires_at > now()")
+            .bind(jti)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .map(|user_uuid| user_uuid.to_string())
+    }
+
+    async fn purge_expired(&mut self) {
+        let _ = sqlx::query("DELETE FROM sessions WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn create_session_should_return_a_session_token() {
+        let mut sessions_service = SessionsImpl::default();
+
+        let session_token = sessions_service.create_session("user_uuid").await;
+
+        assert_eq!(session_token.is_empty(), false);
+    }
+
+    #[tokio::test]
+    async fn validate_session_should_succeed_for_a_fresh_token() {
+        let mut sessions_service = SessionsImpl::default();
+
+        let session_token = sessions_service.create_session("user_uuid").await;
+        let result = sessions_service.validate_session(&session_token).await;
+
+        assert_eq!(result, Some("user_uuid".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn validate_session_should_fail_for_a_malformed_token() {
+        let sessions_service = SessionsImpl::default();
+
+        let result = sessions_service.validate_session("not a real token").await;
+
+        assert_eq!(result.is_none(), true);
+    }
+
+    #[tokio::test]
+    async fn delete_session_should_revoke_the_token() {
+        let mut sessions_service = SessionsImpl::default();
+
+        let session_token = sessions_service.create_session("user_uuid").await;
+        sessions_service.delete_session(&session_token).await;
+        let result = sessions_service.validate_session(&session_token).await;
+
+        assert_eq!(result.is_none(), true);
+    }
+
+    #[tokio::test]
+    async fn purge_expired_should_drop_revoked_entries_past_their_exp() {
+        let mut sessions_service = SessionsImpl::default();
+
+        let session_token = sessions_service.create_session("user_uuid").await;
+        sessions_service.delete_session(&session_token).await;
+        assert_eq!(sessions_service.revoked_jtis.len(), 1);
+
+        // Force the bookkeeping entry into the past so `purge_expired` has something to sweep.
+        for exp in sessions_service.revoked_jtis.values_mut() {
+            *exp = 0;
+        }
+
+        sessions_service.purge_expired().await;
+
+        assert_eq!(sessions_service.revoked_jtis.len(), 0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Interactive SSO/browser sign-in, gated behind the `sso_login` feature so the core gRPC build
+//! stays lean. Modeled on the loopback-listener technique Matrix's SSO login uses: bind an
+//! ephemeral port on `127.0.0.1`, hand the caller a redirect URL pointing at it to open in a
+//! browser, then wait for the single inbound request carrying the identity provider's callback.
+#![cfg(feature = "sso_login")]
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{oneshot, RwLock};
+use uuid::Uuid;
+
+use crate::sessions::Sessions;
+use crate::users::Users;
+
+struct CallbackParams {
+    code: String,
+    state: String,
+}
+
+/// A loopback redirect URL plus the handle that resolves once the identity provider's callback
+/// has landed on it.
+pub struct SsoLogin {
+    pub authorize_url: String,
+    callback: oneshot::Receiver<CallbackParams>,
+    state: String,
+}
+
+impl SsoLogin {
+    /// Binds the loopback listener and builds the URL the caller should open in a browser.
+    /// `idp_authorize_url` is the identity provider's `/authorize` endpoint.
+    pub async fn start(idp_authorize_url: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let state = Uuid::new_v4().to_string();
+        let redirect_uri = format!("http://127.0.0.1:{port}/callback");
+
+        let authorize_url =
+            format!("{idp_authorize_url}?redirect_uri={redirect_uri}&state={state}");
+
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(accept_one_callback(listener, tx));
+
+        Ok(Self {
+            authorize_url,
+            callback: rx,
+            state,
+        })
+    }
+
+    /// Waits for the identity provider's callback, then resolves/provisions the user through
+    /// `users_service` and opens a session through `sessions_service`.
+    pub async fn finish(
+        self,
+        users_service: Arc<RwLock<dyn Users + Send + Sync>>,
+        sessions_service: Arc<RwLock<dyn Sessions + Send + Sync>>,
+    ) -> Result<(String, String), String> {
+        let params = self
+            .callback
+            .await
+            .map_err(|_| "SSO callback listener closed before receiving a callback".to_owned())?;
+
+        if params.state != self.state {
+            return Err("SSO callback state did not match".to_owned());
+        }
+
+        let username = exchange_code_for_username(&params.code)?;
+
+        // First-time SSO sign-in provisions the user, same as a password sign-up would; an
+        // existing username just falls through since `create_user` returning `Err` here is
+        // expected, not fatal. The provisioned password is an opaque, never-shown random UUID:
+        // the user always comes back through SSO, never a password prompt, so resolve the uuid
+        // with `find_user_uuid` afterwards rather than `get_user_uuid`, which would require
+        // supplying that same password back and can never succeed with a fresh random one.
+        let _ = users_service
+            .write()
+            .await
+            .create_user(username.clone(), Uuid::new_v4().to_string())
+            .await;
+
+        let user_uuid = users_service
+            .read()
+            .await
+            .find_user_uuid(&username)
+            .await
+            .ok_or_else(|| "Failed to resolve SSO user".to_owned())?;
+
+        let session_token = sessions_service.write().await.create_session(&user_uuid).await;
+
+        Ok((user_uuid, session_token))
+    }
+}
+
+// Accepts exactly one connection, parses the callback's query params off the request line, and
+// replies with an empty `200 OK`. Errors are swallowed: if nothing
+// ever connects the caller's `finish().await` simply stays pending until dropped.
+async fn accept_one_callback(listener: TcpListener, tx: oneshot::Sender<CallbackParams>) {
+    let Ok((mut stream, _)) = listener.accept().await else {
+        return;
+    };
+
+    let mut buf = [0u8; 4096];
+    let Ok(n) = stream.read(&mut buf).await else {
+        return;
+    };
+
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let Some(request_line) = request.lines().next() else {
+        return;
+    };
+
+    if let Some(params) = parse_callback_params(request_line) {
+        let _ = tx.send(params);
+    }
+
+    let _ = stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+        .await;
+}
+
+// Pulls `code` and `state` out of a request line like `GET /callback?code=...&state=... HTTP/1.1`.
+fn parse_callback_params(request_line: &str) -> Option<CallbackParams> {
+    let path_and_query = request_line.split_whitespace().nth(1)?;
+    let query = path_and_query.split_once('?')?.1;
+
+    let mut code = None;
+    let mut state = None;
+
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=')?;
+        match key {
+            "code" => code = Some(value.to_owned()),
+            "state" => state = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+
+    Some(CallbackParams {
+        code: code?,
+        state: state?,
+    })
+}
+
+// Stand-in for a real identity provider token exchange: trades the authorization code for the
+// identity to provision/sign in as. A production implementation would call the IdP's token
+// endpoint and read the subject out of the returned ID token.
+fn exchange_code_for_username(code: &str) -> Result<String, String> {
+    if code.is_empty() {
+        return Err("Empty authorization code".to_owned());
+    }
+
+    Ok(format!("sso:{code}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_callback_params_should_extract_code_and_state() {
+        let params =
+            parse_callback_params("GET /callback?code=abc123&state=xyz789 HTTP/1.1").unwrap();
+
+        assert_eq!(params.code, "abc123");
+        assert_eq!(params.state, "xyz789");
+    }
+
+    #[test]
+    fn parse_callback_params_should_fail_without_query_string() {
+        let params = parse_callback_params("GET /callback HTTP/1.1");
+
+        assert_eq!(params.is_none(), true);
+    }
+}
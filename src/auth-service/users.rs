@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tonic::async_trait]
+pub trait Users {
+    async fn create_user(&mut self, username: String, password: String) -> Result<(), String>;
+    async fn get_user_uuid(&self, username: String, password: String) -> Option<String>;
+    /// Resolves a `user_uuid` by username alone, with no password check. For flows that have
+    /// already authenticated the user some other way (e.g. SSO), not as a substitute for
+    /// `get_user_uuid` in a password flow.
+    async fn find_user_uuid(&self, username: &str) -> Option<String>;
+    /// Returns the user's TOTP secret, if MFA has been enrolled, so a challenge verifier can
+    /// check codes against it. `None` means the user hasn't enrolled MFA.
+    async fn mfa_secret(&self, user_uuid: &str) -> Option<String>;
+    /// Enrolls (or replaces) the user's TOTP secret.
+    async fn set_mfa_secret(&mut self, user_uuid: &str, secret: String);
+}
+
+#[derive(Default)]
+pub struct UsersImpl {
+    // username -> (user_uuid, password hash in PHC string format, TOTP secret if MFA enrolled)
+    users: HashMap<String, (String, String, Option<String>)>,
+}
+
+#[tonic::async_trait]
+impl Users for UsersImpl {
+    async fn create_user(&mut self, username: String, password: String) -> Result<(), String> {
+        if self.users.contains_key(&username) {
+            return Err("Username already exists".to_owned());
+        }
+
+        // Generate a fresh salt from a CSPRNG and hash the password with Argon2id using the
+        // crate's recommended defaults (~19 MiB memory, 2 iterations, 1 lane).
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| e.to_string())?
+            .to_string();
+
+        let user_uuid = Uuid::new_v4().to_string();
+
+        self.users.insert(username, (user_uuid, password_hash, None));
+
+        Ok(())
+    }
+
+    async fn get_user_uuid(&self, username: String, password: String) -> Option<String> {
+        let (user_uuid, password_hash, _) = self.users.get(&username)?;
+
+        // Parse the stored PHC string and re-derive the hash from the supplied password.
+        // `verify_password` does a constant-time comparison internally.
+        let parsed_hash = PasswordHash::new(password_hash).ok()?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .ok()?;
+
+        Some(user_uuid.clone())
+    }
+
+    async fn find_user_uuid(&self, username: &str) -> Option<String> {
+        self.users.get(username).map(|(uuid, _, _)| uuid.clone())
+    }
+
+    async fn mfa_secret(&self, user_uuid: &str) -> Option<String> {
+        self.users
+            .values()
+            .find(|(uuid, _, _)| uuid == user_uuid)
+            .and_then(|(_, _, secret)| secret.clone())
+    }
+
+    async fn set_mfa_secret(&mut self, user_uuid: &str, secret: String) {
+        if let Some(entry) = self.users.values_mut().find(|(uuid, _, _)| uuid == user_uuid) {
+            entry.2 = Some(secret);
+        }
+    }
+}
+
+/// Postgres-backed `Users`, for when `DATABASE_URL` is set. `Users` is an async trait so this can
+/// `.await` `sqlx` directly instead of bridging sync callers into the async pool with
+/// `block_in_place`/`block_on`, which would require blocking a worker thread while the caller's
+/// `RwLock` guard is held.
+pub struct UsersPg {
+    pool: PgPool,
+}
+
+impl UsersPg {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[tonic::async_trait]
+impl Users for UsersPg {
+    async fn create_user(&mut self, username: String, password: String) -> Result<(), String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| e.to_string())?
+            .to_string();
+
+        let user_uuid = Uuid::new_v4();
+
+        sqlx::query("INSERT INTO users (user_uuid, username, password_hash) VALUES ($1, $2, $3)")
+            .bind(user_uuid)
+            .bind(&username)
+            .bind(&password_hash)
+            .execute(&self.pool)
+            .await
+            // A unique-violation on `username` is the only expected failure mode; map it
+            // to the same `Err(String)` the in-memory backend returns on a duplicate.
+            .map(|_| ())
+            .map_err(|e| match e.as_database_error().and_then(|e| e.code()) {
+                Some(code) if code == "23505" => "Username already exists".to_owned(),
+                _ => e.to_string(),
+            })
+    }
+
+    async fn get_user_uuid(&self, username: String, password: String) -> Option<String> {
+        let (user_uuid, password_hash): (Uuid, String) =
+            sqlx::query_as("SELECT user_uuid, password_hash FROM users WHERE username = $1")
+                .bind(&username)
+                .fetch_optional(&self.pool)
+                .await
+                .ok()
+                .flatten()?;
+
+        let parsed_hash = PasswordHash::new(&password_hash).ok()?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .ok()?;
+
+        Some(user_uuid.to_string())
+    }
+
+    async fn find_user_uuid(&self, username: &str) -> Option<String> {
+        sqlx::query_scalar::<_, Uuid>("SELECT user_uuid FROM users WHERE username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .map(|user_uuid| user_uuid.to_string())
+    }
+
+    async fn mfa_secret(&self, user_uuid: &str) -> Option<String> {
+        let user_uuid: Uuid = user_uuid.parse().ok()?;
+
+        sqlx::query_scalar::<_, Option<String>>("SELECT mfa_secret FROM users WHERE user_uuid = $1")
+            .bind(user_uuid)
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .flatten()
+    }
+
+    async fn set_mfa_secret(&mut self, user_uuid: &str, secret: String) {
+        let Ok(user_uuid) = user_uuid.parse::<Uuid>() else {
+            return;
+        };
+
+        let _ = sqlx::query("UPDATE users SET mfa_secret = $1 WHERE user_uuid = $2")
+            .bind(secret)
+            .bind(user_uuid)
+            .execute(&self.pool)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn create_user_should_succeed() {
+        let mut users_service = UsersImpl::default();
+
+        let result = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[tokio::test]
+    async fn create_user_should_fail_if_username_exists() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+        let result = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[tokio::test]
+    async fn stored_password_is_not_plaintext() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+        let (_, password_hash, _) = users_service.users.get("username").unwrap();
+
+        assert_ne!(password_hash, "password");
+        assert!(password_hash.starts_with("$argon2id$"));
+    }
+
+    #[tokio::test]
+    async fn get_user_uuid_should_fail_if_user_not_found() {
+        let users_service = UsersImpl::default();
+
+        let result = users_service.get_user_uuid("username".to_owned(), "password".to_owned()).await;
+
+        assert_eq!(result.is_none(), true);
+    }
+
+    #[tokio::test]
+    async fn get_user_uuid_should_fail_if_incorrect_password() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+        let result =
+            users_service.get_user_uuid("username".to_owned(), "wrong password".to_owned()).await;
+
+        assert_eq!(result.is_none(), true);
+    }
+
+    #[tokio::test]
+    async fn get_user_uuid_should_succeed() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+        let result = users_service.get_user_uuid("username".to_owned(), "password".to_owned()).await;
+
+        assert_eq!(result.is_some(), true);
+    }
+
+    #[tokio::test]
+    async fn mfa_secret_should_be_none_until_enrolled() {
+        let mut users_service = UsersImpl::default();
+
+        let _ = users_service.create_user("username".to_owned(), "password".to_owned()).await;
+        let user_uuid =
+            users_service.get_user_uuid("username".to_owned(), "password".to_owned()).await.unwrap();
+
+        assert_eq!(users_service.mfa_secret(&user_uuid).await, None);
+
+        users_service.set_mfa_secret(&user_uuid, "supersecret".to_owned()).await;
+
+        assert_eq!(users_service.mfa_secret(&user_uuid).await, Some("supersecret".to_owned()));
+    }
+}